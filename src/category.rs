@@ -0,0 +1,86 @@
+// Copyright (c) 2024 Future Internet Consulting and Development Solutions S.L.
+use std::collections::HashSet;
+use unicode_general_category::{get_general_category, GeneralCategory};
+
+/// Every two-letter Unicode general-category abbreviation a caller may pass
+/// to `strip_categories`.
+const ALL_ABBREVIATIONS: &[&str] = &[
+    "Lu", "Ll", "Lt", "Lm", "Lo", "Mn", "Mc", "Me", "Nd", "Nl", "No", "Pc", "Pd", "Ps", "Pe", "Pi",
+    "Pf", "Po", "Sm", "Sc", "Sk", "So", "Zs", "Zl", "Zp", "Cc", "Cf", "Cs", "Co", "Cn",
+];
+
+/// Maps a [`GeneralCategory`] to its two-letter Unicode abbreviation, e.g.
+/// `Control` -> `"Cc"`, `OtherSymbol` -> `"So"`.
+fn abbreviation(category: GeneralCategory) -> &'static str {
+    use GeneralCategory::*;
+    match category {
+        UppercaseLetter => "Lu",
+        LowercaseLetter => "Ll",
+        TitlecaseLetter => "Lt",
+        ModifierLetter => "Lm",
+        OtherLetter => "Lo",
+        NonspacingMark => "Mn",
+        SpacingMark => "Mc",
+        EnclosingMark => "Me",
+        DecimalNumber => "Nd",
+        LetterNumber => "Nl",
+        OtherNumber => "No",
+        ConnectorPunctuation => "Pc",
+        DashPunctuation => "Pd",
+        OpenPunctuation => "Ps",
+        ClosePunctuation => "Pe",
+        InitialPunctuation => "Pi",
+        FinalPunctuation => "Pf",
+        OtherPunctuation => "Po",
+        MathSymbol => "Sm",
+        CurrencySymbol => "Sc",
+        ModifierSymbol => "Sk",
+        OtherSymbol => "So",
+        SpaceSeparator => "Zs",
+        LineSeparator => "Zl",
+        ParagraphSeparator => "Zp",
+        Control => "Cc",
+        Format => "Cf",
+        Surrogate => "Cs",
+        PrivateUse => "Co",
+        Unassigned => "Cn",
+    }
+}
+
+/// A configured set of Unicode general categories (by two-letter
+/// abbreviation, e.g. `"Cc"`, `"So"`) to drop from normalized output.
+/// Unknown abbreviations passed in by the caller are silently ignored,
+/// matching the "unsupported flag is a no-op" style `basic_string_clean`
+/// already uses for its other boolean knobs.
+///
+/// This is additive on top of `IsEmoji::is_char_to_avoid`'s hardcoded
+/// control-char/non-BMP filtering (`src/emoji.rs`), not a replacement for
+/// it — there's no way to configure `strip_categories` to keep those back.
+pub struct CategoryFilter {
+    strip: HashSet<&'static str>,
+}
+
+impl CategoryFilter {
+    pub fn new(strip_categories: &[String]) -> Self {
+        let strip = strip_categories
+            .iter()
+            .filter_map(|requested| {
+                ALL_ABBREVIATIONS
+                    .iter()
+                    .find(|known| *known == requested)
+                    .copied()
+            })
+            .collect();
+        CategoryFilter { strip }
+    }
+
+    pub fn none() -> Self {
+        CategoryFilter {
+            strip: HashSet::new(),
+        }
+    }
+
+    pub fn should_strip(&self, c: char) -> bool {
+        !self.strip.is_empty() && self.strip.contains(abbreviation(get_general_category(c)))
+    }
+}
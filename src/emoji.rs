@@ -0,0 +1,380 @@
+// Copyright (c) 2024 Future Internet Consulting and Development Solutions S.L.
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+/// Per-scalar and per-sequence classification helpers used by the
+/// normalization pipeline in `lib.rs`.
+pub trait IsEmoji {
+    /// Whether this scalar, on its own, is considered part of an emoji
+    /// (pictographs, dingbats, regional indicators, skin-tone modifiers,
+    /// variation selectors and joiners used to build up emoji sequences).
+    fn is_emoji(&self) -> bool;
+
+    /// Whether this scalar should never survive compatibility decomposition:
+    /// control characters, characters outside the Basic Multilingual Plane,
+    /// and the emoji presentation/keycap marks that have no meaning on
+    /// their own once separated from the scalar they decorate.
+    fn is_char_to_avoid(&self) -> bool;
+}
+
+impl IsEmoji for char {
+    fn is_emoji(&self) -> bool {
+        is_emoji_base(*self)
+            || self.is_emoji_modifier()
+            || matches!(*self, '\u{200D}' | '\u{FE0E}' | '\u{FE0F}' | '\u{20E3}')
+    }
+
+    fn is_char_to_avoid(&self) -> bool {
+        let cp = *self as u32;
+        cp > 0xFFFF || self.is_control() || matches!(*self, '\u{FE0E}' | '\u{FE0F}' | '\u{20E3}')
+    }
+}
+
+trait IsEmojiModifier {
+    fn is_emoji_modifier(&self) -> bool;
+}
+
+impl IsEmojiModifier for char {
+    fn is_emoji_modifier(&self) -> bool {
+        matches!(*self as u32, 0x1F3FB..=0x1F3FF)
+    }
+}
+
+/// CLDR short names for single-scalar emoji, e.g. `❤` -> `red_heart`.
+///
+/// A small static list searched linearly (see [`longest_emoji_match`]) —
+/// not worth a `HashMap` or a sorted/binary-searched table for a couple
+/// dozen entries that never change at runtime. An entry whose scalar is
+/// followed by an `Emoji_Modifier` (U+1F3FB..=U+1F3FF) gets a skin-tone
+/// suffix appended to its name on the fly in [`longest_emoji_match`], so we
+/// don't need one table row per skin tone.
+static EMOJI_NAMES: &[(char, &str)] = &[
+    ('\u{2764}', "red_heart"),
+    ('\u{2705}', "check_mark_button"),
+    ('\u{2733}', "eight_spoked_asterisk"),
+    ('\u{1F44D}', "thumbs_up"),
+    ('\u{1F44E}', "thumbs_down"),
+    ('\u{1F525}', "fire"),
+    ('\u{1F600}', "grinning_face"),
+    ('\u{1F602}', "face_with_tears_of_joy"),
+    ('\u{1F60D}', "smiling_face_with_heart_eyes"),
+    ('\u{1F642}', "slightly_smiling_face"),
+    ('\u{1F680}', "rocket"),
+    ('\u{1F382}', "birthday_cake"),
+    ('\u{1F512}', "locked"),
+    ('\u{1F52C}', "microscope"),
+    ('\u{1F4AF}', "hundred_points"),
+    ('\u{1F4C5}', "calendar"),
+    ('\u{1F4CC}', "pushpin"),
+    ('\u{1F389}', "party_popper"),
+    ('\u{1F9B0}', "red_hair"),
+    ('\u{1F9B3}', "white_hair"),
+    ('\u{1FAC0}', "anatomical_heart"),
+    ('\u{1FABD}', "wing"),
+    ('\u{1F502}', "repeat_single_button"),
+];
+
+/// ASCII scalars that can start a keycap sequence, mapped to the name that
+/// [`longest_emoji_match`] emits after a `keycap_` prefix, e.g. `#️⃣`
+/// (`#` + VS16 + keycap) -> `keycap_hash`.
+static KEYCAP_BASES: &[(char, &str)] = &[
+    ('#', "hash"),
+    ('*', "asterisk"),
+    ('0', "zero"),
+    ('1', "one"),
+    ('2', "two"),
+    ('3', "three"),
+    ('4', "four"),
+    ('5', "five"),
+    ('6', "six"),
+    ('7', "seven"),
+    ('8', "eight"),
+    ('9', "nine"),
+];
+
+/// A handful of multi-scalar ZWJ sequences that are common enough to be
+/// worth naming explicitly rather than relying on the per-scalar fallback.
+static SEQUENCE_NAMES: &[(&[char], &str)] = &[
+    (&['\u{1F468}', '\u{200D}', '\u{1F469}', '\u{200D}', '\u{1F467}'], "family_man_woman_girl"),
+    (&['\u{2764}', '\u{FE0F}', '\u{200D}', '\u{1F525}'], "heart_on_fire"),
+];
+
+fn skin_tone_name(modifier: char) -> &'static str {
+    match modifier as u32 {
+        0x1F3FB => "light_skin_tone",
+        0x1F3FC => "medium_light_skin_tone",
+        0x1F3FD => "medium_skin_tone",
+        0x1F3FE => "medium_dark_skin_tone",
+        0x1F3FF => "dark_skin_tone",
+        _ => unreachable!("caller already checked is_emoji_modifier"),
+    }
+}
+
+fn regional_indicator_letter(c: char) -> Option<char> {
+    match c as u32 {
+        0x1F1E6..=0x1F1FF => Some((b'A' + (c as u32 - 0x1F1E6) as u8) as char),
+        _ => None,
+    }
+}
+
+/// Whether `c` is the start of an emoji on its own (a pictograph, dingbat or
+/// regional indicator), as opposed to a modifier/joiner/variation selector
+/// that only has meaning attached to one. Excludes the handful of Dingbats
+/// scalars (see [`is_text_presentation_default`]) that render as plain text
+/// unless explicitly decorated with `U+FE0F` — `remove_emojis` must leave
+/// those bare scalars untouched.
+fn is_emoji_base(c: char) -> bool {
+    let cp = c as u32;
+    matches!(
+        cp,
+        0x203C | 0x2049
+        | 0x2122 | 0x2139
+        | 0x2194..=0x21AA
+        | 0x231A..=0x231B
+        | 0x2300..=0x23FA
+        | 0x24C2
+        | 0x25AA..=0x25FE
+        | 0x2600..=0x27BF
+        | 0x2934..=0x2935
+        | 0x2B00..=0x2BFF
+        | 0x3030 | 0x303D
+        | 0x3297 | 0x3299
+        | 0x1F000..=0x1FAFF
+    ) && !is_text_presentation_default(c)
+}
+
+/// Dingbats scalars that are `Emoji_Presentation=No`: they render as plain
+/// text by default and only become emoji once explicitly decorated with
+/// `U+FE0F`, e.g. `✳` vs. `✳️`. `demojize` still needs to recognise
+/// `scalar (+ U+FE0F)?` as a nameable unit, so [`unit_len`] treats these as
+/// valid bases even though [`is_emoji_base`] (and therefore `is_emoji()`,
+/// which gates `remove_emojis`) does not.
+pub fn is_text_presentation_default(c: char) -> bool {
+    matches!(c, '\u{2733}' | '\u{2764}')
+}
+
+/// Whether `c` is an ASCII scalar that can start a keycap sequence
+/// (`#`, `*`, `0`-`9`), e.g. the `#` in `#️⃣`. These are never emoji on
+/// their own — only `demojize`'s cluster lookahead treats them as
+/// candidates, since `remove_emojis` must leave a bare `#` untouched.
+pub fn is_keycap_base(c: char) -> bool {
+    KEYCAP_BASES.iter().any(|(base, _)| *base == c)
+}
+
+/// Consumes a single emoji "unit": a keycap sequence, or a base scalar plus
+/// its optional skin-tone modifier and/or variation selector. Returns the
+/// number of scalars consumed, or `None` if `chars` doesn't start with one.
+fn unit_len(chars: &[char]) -> Option<usize> {
+    if chars.is_empty() {
+        return None;
+    }
+
+    if is_keycap_base(chars[0]) {
+        let mut len = 1;
+        if chars.get(len) == Some(&'\u{FE0F}') {
+            len += 1;
+        }
+        return (chars.get(len) == Some(&'\u{20E3}')).then_some(len + 1);
+    }
+
+    if !is_emoji_base(chars[0]) && !is_text_presentation_default(chars[0]) {
+        return None;
+    }
+    let mut len = 1;
+    if chars.get(len).is_some_and(|c| c.is_emoji_modifier()) {
+        len += 1;
+    }
+    if matches!(chars.get(len), Some(&'\u{FE0E}') | Some(&'\u{FE0F}')) {
+        len += 1;
+    }
+    Some(len)
+}
+
+/// Segments the emoji grapheme cluster starting at `chars[0]`, so the
+/// normalization loop can skip or replace it as one atomic unit instead of
+/// leaving behind orphaned joiners or variation selectors. Handles flag
+/// sequences (pairs of Regional Indicators), keycaps, skin-tone modifiers,
+/// and chains of `U+200D`-joined units (ZWJ sequences such as family or
+/// couple emoji). Returns `None` when `chars[0]` doesn't start an emoji.
+pub fn emoji_cluster_len(chars: &[char]) -> Option<usize> {
+    if let (Some(_), Some(_)) = (
+        regional_indicator_letter(*chars.first()?),
+        chars.get(1).and_then(|c| regional_indicator_letter(*c)),
+    ) {
+        return Some(2);
+    }
+
+    let mut len = unit_len(chars)?;
+    while chars.get(len) == Some(&'\u{200D}') {
+        match unit_len(&chars[len + 1..]) {
+            Some(next_len) => len += 1 + next_len,
+            None => break,
+        }
+    }
+    Some(len)
+}
+
+/// Finds the best CLDR-style short name for the emoji cluster starting at
+/// `chars[0]` and returns how many scalars it spans together with the name.
+/// Returns `None` when `chars[0]` is not the start of a recognised emoji, or
+/// when the cluster has no known name (e.g. an unlisted ZWJ sequence) — in
+/// which case the caller still has [`emoji_cluster_len`] to skip it atomically.
+pub fn longest_emoji_match(chars: &[char]) -> Option<(usize, String)> {
+    let cluster_len = emoji_cluster_len(chars)?;
+    let cluster = &chars[..cluster_len];
+
+    for (seq, name) in SEQUENCE_NAMES {
+        if cluster == *seq {
+            return Some((cluster_len, name.to_string()));
+        }
+    }
+
+    if let (Some(a), Some(b)) = (
+        regional_indicator_letter(chars[0]),
+        chars.get(1).and_then(|c| regional_indicator_letter(*c)),
+    ) {
+        return Some((2, format!("flag_{}{}", a, b).to_lowercase()));
+    }
+
+    if let Some((_, base_name)) = KEYCAP_BASES.iter().find(|(base, _)| *base == chars[0]) {
+        if unit_len(chars) == Some(cluster_len) {
+            return Some((cluster_len, format!("keycap_{}", base_name)));
+        }
+    }
+
+    let (_, base_name) = EMOJI_NAMES.iter().find(|(scalar, _)| *scalar == chars[0])?;
+    let mut name = base_name.to_string();
+    let mut consumed = 1;
+    if let Some(modifier) = chars.get(consumed).copied().filter(|c| c.is_emoji_modifier()) {
+        name.push('_');
+        name.push_str(skin_tone_name(modifier));
+        consumed += 1;
+    }
+    // A trailing variation selector (the `U+FE0F` in e.g. `❤️`) only forces
+    // emoji presentation; it doesn't change which name applies, so strip it
+    // before deciding whether anything unrecognised is left over.
+    if matches!(chars.get(consumed), Some(&'\u{FE0E}') | Some(&'\u{FE0F}')) {
+        consumed += 1;
+    }
+    if consumed != cluster_len {
+        return None;
+    }
+    Some((cluster_len, name))
+}
+
+lazy_static! {
+    static ref NAME_TO_EMOJI: HashMap<String, String> = {
+        let mut map = HashMap::new();
+        for (scalar, name) in EMOJI_NAMES {
+            map.insert(name.to_string(), scalar.to_string());
+            for modifier in '\u{1F3FB}'..='\u{1F3FF}' {
+                map.insert(format!("{}_{}", name, skin_tone_name(modifier)), format!("{}{}", scalar, modifier));
+            }
+        }
+        for (base, name) in KEYCAP_BASES {
+            map.insert(format!("keycap_{}", name), format!("{}\u{FE0F}\u{20E3}", base));
+        }
+        for a in 0..26u32 {
+            for b in 0..26u32 {
+                let flag: String = [a, b].iter().filter_map(|i| char::from_u32(0x1F1E6 + i)).collect();
+                map.insert(format!("flag_{}{}", (b'a' + a as u8) as char, (b'a' + b as u8) as char), flag);
+            }
+        }
+        for (seq, name) in SEQUENCE_NAMES {
+            map.insert(name.to_string(), seq.iter().collect());
+        }
+        map
+    };
+}
+
+/// Replaces `:name:`-style tokens (as produced by `demojize`) back with
+/// their emoji. Tokens that don't match a known name are left untouched.
+pub fn emojize(value: &str, delimiters: (&str, &str)) -> String {
+    let (open, close) = delimiters;
+    if open.is_empty() || close.is_empty() {
+        return value.to_string();
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(start) = rest.find(open) {
+        result.push_str(&rest[..start]);
+        let after_open = &rest[start + open.len()..];
+        match after_open.find(close) {
+            Some(end) => {
+                let name = &after_open[..end];
+                match NAME_TO_EMOJI.get(name) {
+                    Some(emoji) => result.push_str(emoji),
+                    None => {
+                        result.push_str(open);
+                        result.push_str(name);
+                        result.push_str(close);
+                    }
+                }
+                rest = &after_open[end + close.len()..];
+            }
+            None => {
+                result.push_str(open);
+                result.push_str(after_open);
+                rest = "";
+                break;
+            }
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// Emoji version each range of scalars was introduced in, sorted by start
+/// codepoint. Versions are the well-known milestones (0.6, 1.0, 2.0, 3.0,
+/// 5.0, 12.0) rather than a scalar-by-scalar replica of the
+/// full Unicode `emoji-data.txt`, which is enough to let callers filter out
+/// emoji newer than a renderer/font's supported set.
+static EMOJI_VERSIONS: &[(u32, u32, f32)] = &[
+    (0x203C, 0x203C, 3.0),
+    (0x2049, 0x2049, 3.0),
+    (0x2122, 0x2139, 3.0),
+    (0x2194, 0x21AA, 3.0),
+    (0x2300, 0x23FA, 0.6),
+    (0x231A, 0x231B, 0.6),
+    (0x24C2, 0x24C2, 0.6),
+    (0x25AA, 0x25FE, 0.6),
+    (0x2600, 0x27BF, 0.6),
+    (0x2934, 0x2935, 3.0),
+    (0x2B00, 0x2BFF, 0.6),
+    (0x3030, 0x3030, 0.6),
+    (0x303D, 0x303D, 0.6),
+    (0x3297, 0x3297, 0.6),
+    (0x3299, 0x3299, 0.6),
+    (0x1F1E6, 0x1F1FF, 2.0), // regional indicators / flags
+    (0x1F300, 0x1F5FF, 1.0),
+    (0x1F600, 0x1F64F, 1.0),
+    (0x1F680, 0x1F6FF, 1.0),
+    (0x1F900, 0x1F9FF, 5.0),
+    (0x1FA70, 0x1FAFF, 12.0),
+];
+
+/// Looks up the Emoji spec version a scalar was introduced in. Returns
+/// `None` for scalars outside [`EMOJI_VERSIONS`]'s coverage (keycap bases,
+/// variation selectors, joiners — these never gate removal on their own, only the
+/// base pictograph of a cluster does).
+pub fn emoji_version(c: char) -> Option<f32> {
+    let cp = c as u32;
+    EMOJI_VERSIONS
+        .iter()
+        .find(|(start, end, _)| (*start..=*end).contains(&cp))
+        .map(|(_, _, version)| *version)
+}
+
+/// Whether `c` falls inside the `[min_version, max_version]` window
+/// (either bound optional). Scalars with no known version (see
+/// [`emoji_version`]) are treated as version `0.0`, so an unbounded
+/// `min_version` still lets them through. When neither bound is set, every
+/// scalar is considered in-window — i.e. this is a no-op filter.
+pub fn is_in_emoji_version_window(c: char, min_version: Option<f32>, max_version: Option<f32>) -> bool {
+    if min_version.is_none() && max_version.is_none() {
+        return true;
+    }
+    let version = emoji_version(c).unwrap_or(0.0);
+    min_version.is_none_or(|min| version >= min) && max_version.is_none_or(|max| version <= max)
+}
@@ -1,10 +1,13 @@
 // Copyright (c) 2024 Future Internet Consulting and Development Solutions S.L.
+mod category;
 mod emoji;
 
+use category::CategoryFilter;
 use emoji::IsEmoji;
 use lazy_static::lazy_static;
 use pyo3::prelude::*;
 use regex::Regex;
+use std::collections::VecDeque;
 use unicode_normalization::char::decompose_compatible;
 use unicode_normalization::UnicodeNormalization;
 
@@ -12,108 +15,577 @@ lazy_static! {
     static ref EMOJI_RE: Regex = Regex::new(r"[\p{Emoji_Presentation}\p{Emoji_Modifier}\p{Emoji_Modifier_Base}\{Cc}\uFE0E\uFE0F\u20E2\u20E3\u20E4]").unwrap();
 }
 
-/// Gives the normalized form of a string skipping some characters.
-fn custom_normalization(
-    str: String,
-    allow_chars: Vec<char>,
-    collapse_whitespace: bool,
-    remove_emojis: bool,
-) -> String {
-    let mut result = String::with_capacity(str.len());
-    let mut previous_whitespace = false;
-    for c in str.chars() {
-        previous_whitespace = custom_character_normalization(
-            &mut result,
-            c,
-            &allow_chars,
-            collapse_whitespace,
-            previous_whitespace,
-            remove_emojis,
-        );
+/// A tiny sorted allow-list of scalars that always pass through
+/// normalization untouched, searched in O(log n) instead of the linear
+/// `Vec<char>::contains` scan this replaces. The ordinal indicators are
+/// always present; tab and EOL are opt-in, so the whole set fits inline
+/// without allocating.
+#[derive(Clone, Copy)]
+struct AllowedChars {
+    chars: [char; 5],
+    len: usize,
+}
+
+impl AllowedChars {
+    const BASE: [char; 2] = ['\u{BA}', '\u{AA}'];
+
+    fn new(allow_tab: bool, allow_eol: bool) -> Self {
+        let mut chars = ['\0'; 5];
+        let mut len = 0;
+        for c in Self::BASE {
+            chars[len] = c;
+            len += 1;
+        }
+        if allow_tab {
+            chars[len] = '\t';
+            len += 1;
+        }
+        if allow_eol {
+            chars[len] = '\n';
+            len += 1;
+            chars[len] = '\r';
+            len += 1;
+        }
+        chars[..len].sort_unstable();
+        AllowedChars { chars, len }
+    }
+
+    fn base() -> Self {
+        Self::new(false, false)
+    }
+
+    fn contains(&self, c: char) -> bool {
+        self.chars[..self.len].binary_search(&c).is_ok()
     }
-    result.nfc().collect::<String>()
 }
 
-fn custom_character_normalization(
-    str: &mut String,
-    c: char,
-    allow_chars: &Vec<char>,
+/// Streaming `Iterator<Item = char>` adapter that applies the allow-list,
+/// whitespace collapsing, emoji handling and `is_char_to_avoid` filtering
+/// one character at a time, so it can be fed directly into
+/// `unicode_normalization`'s lazy `.nfc()` iterator instead of materializing
+/// an intermediate `String`.
+struct NormalizeChars<'d, I: Iterator<Item = char>> {
+    chars: I,
+    allow_chars: AllowedChars,
     collapse_whitespace: bool,
-    previous_whitespace: bool,
     remove_emojis: bool,
-) -> bool {
-    if allow_chars.contains(&c) {
-        str.push(c);
-        return false;
-    } else if c.is_whitespace() {
-        if !collapse_whitespace || !previous_whitespace {
-            str.push(' ')
-        }
-        return true;
-    } else if remove_emojis && c.is_emoji() {
-        return previous_whitespace;
+    replace_emojis: bool,
+    delimiters: (&'d str, &'d str),
+    category_filter: &'d CategoryFilter,
+    min_emoji_version: Option<f32>,
+    max_emoji_version: Option<f32>,
+    previous_whitespace: bool,
+    // Decomposed/replacement chars queued for output, and raw input chars
+    // pulled ahead of the cursor while sizing an emoji cluster.
+    pending: VecDeque<char>,
+    lookahead: VecDeque<char>,
+}
+
+impl<'d, I: Iterator<Item = char>> NormalizeChars<'d, I> {
+    fn pull(&mut self) -> Option<char> {
+        self.lookahead.pop_front().or_else(|| self.chars.next())
     }
 
-    let mut pushed = false;
-    decompose_compatible(c, |r| {
-        // Ignore characters outside the Basic Multilingual Plane, Control chars, etc
-        if !r.is_char_to_avoid() {
-            str.push(r);
-            pushed = true;
+    /// Pulls just enough chars to size the emoji cluster starting at
+    /// `first`, growing the window while it might still be extended (e.g.
+    /// by a following skin-tone modifier or `U+200D` continuation).
+    fn cluster_window(&mut self, first: char) -> Vec<char> {
+        const MAX_WINDOW: usize = 64;
+        let mut window = vec![first];
+
+        if emoji::is_keycap_base(first) {
+            // A keycap sequence is `base (+ U+FE0F)? + U+20E3` — at most 3
+            // scalars — and `emoji_cluster_len` can't confirm a match until
+            // the whole thing has been pulled, so just look that far ahead
+            // instead of relying on the incremental growth check below.
+            for _ in 0..2 {
+                match self.pull() {
+                    Some(c) => window.push(c),
+                    None => break,
+                }
+            }
+            return window;
         }
-    });
 
-    if pushed {
-        false
-    } else {
-        previous_whitespace
+        while window.len() < MAX_WINDOW {
+            // Keep pulling while the whole window is one cluster, or is one
+            // cluster plus a trailing `U+200D` still waiting for its next unit.
+            let matched = emoji::emoji_cluster_len(&window);
+            let may_extend = matched == Some(window.len())
+                || (window.last() == Some(&'\u{200D}') && matched == Some(window.len() - 1));
+            if !may_extend {
+                break;
+            }
+            match self.pull() {
+                Some(c) => window.push(c),
+                None => break,
+            }
+        }
+        window
     }
 }
 
+impl<'d, I: Iterator<Item = char>> Iterator for NormalizeChars<'d, I> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        loop {
+            if let Some(c) = self.pending.pop_front() {
+                return Some(c);
+            }
+
+            let c = self.pull()?;
+
+            if self.allow_chars.contains(c) {
+                self.previous_whitespace = false;
+                return Some(c);
+            }
+
+            if c.is_whitespace() {
+                let emit = !self.collapse_whitespace || !self.previous_whitespace;
+                self.previous_whitespace = true;
+                if emit {
+                    return Some(' ');
+                }
+                continue;
+            }
+
+            // A bare keycap base (`#`, `*`, a digit) or a Dingbats scalar
+            // with `Emoji_Presentation=No` (e.g. `❤`, `✳`) is only
+            // interesting when we're about to name it (`replace_emojis`):
+            // on its own it's ordinary text, and `remove_emojis` must leave
+            // it untouched.
+            let names_only_candidate = self.replace_emojis
+                && (emoji::is_keycap_base(c) || emoji::is_text_presentation_default(c));
+
+            if self.remove_emojis && (c.is_emoji() || names_only_candidate) {
+                // Consume the whole emoji grapheme cluster (flag pair,
+                // keycap, skin-tone modifier, ZWJ sequence...) atomically so
+                // no stray joiner or variation selector is left behind.
+                let window = self.cluster_window(c);
+                let matched_len = emoji::emoji_cluster_len(&window);
+                // A keycap base with no keycap tail (e.g. a standalone `#`)
+                // didn't actually start a cluster; fall through and let it
+                // be handled like any other character instead of stripping it.
+                let is_cluster = matched_len.is_some() || c.is_emoji();
+                if is_cluster {
+                    let cluster_len = matched_len.unwrap_or(1);
+                    for extra in window[cluster_len..].iter().rev() {
+                        self.lookahead.push_front(*extra);
+                    }
+                    // The version window only gates removal of a genuinely
+                    // recognised cluster (`matched_len`); an orphan
+                    // decoration mark with no base of its own (a stray
+                    // joiner/modifier/variation selector, `matched_len ==
+                    // None`) never survives on its own, window or not.
+                    let outside_version_window = matched_len.is_some()
+                        && !emoji::is_in_emoji_version_window(
+                            c,
+                            self.min_emoji_version,
+                            self.max_emoji_version,
+                        );
+                    if outside_version_window {
+                        // Outside the configured version window: emit the
+                        // cluster's scalars verbatim instead of falling
+                        // through to `decompose_compatible`/`is_char_to_avoid`
+                        // below, which drops every non-BMP pictograph and
+                        // would leave orphaned `U+200D` joiners behind for
+                        // multi-scalar clusters.
+                        self.pending.extend(window[..cluster_len].iter().copied());
+                        self.previous_whitespace = false;
+                    } else if self.replace_emojis {
+                        if let Some((_, name)) = emoji::longest_emoji_match(&window[..cluster_len])
+                        {
+                            self.pending.extend(self.delimiters.0.chars());
+                            self.pending.extend(name.chars());
+                            self.pending.extend(self.delimiters.1.chars());
+                            self.previous_whitespace = false;
+                        }
+                    }
+                    continue;
+                }
+                for extra in window[1..].iter().rev() {
+                    self.lookahead.push_front(*extra);
+                }
+            }
+
+            let mut pushed = false;
+            decompose_compatible(c, |r| {
+                // Ignore characters outside the Basic Multilingual Plane, Control chars, etc
+                if !r.is_char_to_avoid() && !self.category_filter.should_strip(r) {
+                    self.pending.push_back(r);
+                    pushed = true;
+                }
+            });
+            if pushed {
+                self.previous_whitespace = false;
+            }
+        }
+    }
+}
+
+/// Gives the normalized form of a string skipping some characters, in a
+/// single streaming pass: filtering and NFC composition happen lazily over
+/// the same iterator chain instead of two separate full-string passes.
+///
+/// `remove_emojis` drops emoji from the output; `replace_emojis` swaps them
+/// for their CLDR short name wrapped in `delimiters` instead, e.g.
+/// `:red_heart:`. `replace_emojis` implies `remove_emojis` — asking for
+/// emoji to be replaced by their name means they're never emitted as-is.
+#[allow(clippy::too_many_arguments)]
+fn custom_normalization(
+    str: &str,
+    allow_chars: AllowedChars,
+    collapse_whitespace: bool,
+    remove_emojis: bool,
+    replace_emojis: bool,
+    delimiters: (&str, &str),
+    category_filter: &CategoryFilter,
+    min_emoji_version: Option<f32>,
+    max_emoji_version: Option<f32>,
+) -> String {
+    let normalized = NormalizeChars {
+        chars: str.chars(),
+        allow_chars,
+        collapse_whitespace,
+        remove_emojis: remove_emojis || replace_emojis,
+        replace_emojis,
+        delimiters,
+        category_filter,
+        min_emoji_version,
+        max_emoji_version,
+        previous_whitespace: false,
+        pending: VecDeque::new(),
+        lookahead: VecDeque::new(),
+    };
+    let mut result = String::with_capacity(str.len());
+    result.extend(normalized.nfc());
+    result
+}
+
 #[pyfunction]
-#[pyo3(signature = (value, allow_tab=false, allow_eol=true, collapse_whitespace=false, remove_emojis=false))]
+#[pyo3(signature = (value, allow_tab=false, allow_eol=true, collapse_whitespace=false, remove_emojis=false, replace_emojis=false, delimiters=(":".to_string(), ":".to_string()), strip_categories=vec![], min_emoji_version=None, max_emoji_version=None))]
+#[allow(clippy::too_many_arguments)]
 fn basic_string_clean(
     value: String,
     allow_tab: bool,
     allow_eol: bool,
     collapse_whitespace: bool,
     remove_emojis: bool,
+    replace_emojis: bool,
+    delimiters: (String, String),
+    strip_categories: Vec<String>,
+    min_emoji_version: Option<f32>,
+    max_emoji_version: Option<f32>,
 ) -> PyResult<String> {
-    let mut allowed_chars = vec!['º', 'ª'];
-    if allow_tab {
-        allowed_chars.push('\t');
-    }
-    if allow_eol {
-        allowed_chars.push('\n');
-        allowed_chars.push('\r');
-    }
-
-    Ok(
-        custom_normalization(value, allowed_chars, collapse_whitespace, remove_emojis)
-            .trim()
-            .to_string(),
+    Ok(custom_normalization(
+        &value,
+        AllowedChars::new(allow_tab, allow_eol),
+        collapse_whitespace,
+        remove_emojis,
+        replace_emojis,
+        (delimiters.0.as_str(), delimiters.1.as_str()),
+        &CategoryFilter::new(&strip_categories),
+        min_emoji_version,
+        max_emoji_version,
     )
+    .trim()
+    .to_string())
 }
 
 #[pyfunction]
 fn remove_emojis(value: String) -> PyResult<String> {
-    let result = custom_normalization(value, vec!['º', 'ª'], true, true);
+    let result = custom_normalization(
+        &value,
+        AllowedChars::base(),
+        true,
+        true,
+        false,
+        (":", ":"),
+        &CategoryFilter::none(),
+        None,
+        None,
+    );
     Ok(result.trim().to_string())
 }
 
+/// Replaces emoji in `value` with their CLDR short name wrapped in
+/// `delimiters`, e.g. `demojize("I ❤ Rust")` -> `"I :red_heart: Rust"`.
+///
+/// This is the counterpart to [`remove_emojis`] for callers (log/search
+/// indexing, ...) that need emoji to survive normalization as ASCII-safe
+/// tokens instead of being dropped.
+#[pyfunction]
+#[pyo3(signature = (value, delimiters=(":".to_string(), ":".to_string()), min_emoji_version=None, max_emoji_version=None))]
+fn demojize(
+    value: String,
+    delimiters: (String, String),
+    min_emoji_version: Option<f32>,
+    max_emoji_version: Option<f32>,
+) -> PyResult<String> {
+    let result = custom_normalization(
+        &value,
+        AllowedChars::base(),
+        true,
+        true,
+        true,
+        (delimiters.0.as_str(), delimiters.1.as_str()),
+        &CategoryFilter::none(),
+        min_emoji_version,
+        max_emoji_version,
+    );
+    Ok(result.trim().to_string())
+}
+
+/// Replaces `:name:`-style tokens produced by [`demojize`] back with their
+/// emoji. Unknown tokens are left untouched.
+#[pyfunction]
+#[pyo3(signature = (value, delimiters=(":".to_string(), ":".to_string())))]
+fn emojize(value: String, delimiters: (String, String)) -> PyResult<String> {
+    Ok(emoji::emojize(&value, (delimiters.0.as_str(), delimiters.1.as_str())))
+}
+
+/// Splits `value` into text and emoji runs, reusing the same grapheme
+/// cluster segmenter as [`remove_emojis`]/[`demojize`], and returns each run
+/// as a `(text, is_emoji, start_char, end_char)` tuple (character offsets,
+/// not bytes). A read-only companion to the cleaners for callers that want
+/// to decide per-token what to do instead of the all-or-nothing cleaners.
+#[pyfunction]
+fn analyze(value: String) -> PyResult<Vec<(String, bool, usize, usize)>> {
+    let chars: Vec<char> = value.chars().collect();
+    let mut tokens = Vec::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let cluster_len = if chars[i].is_emoji() {
+            emoji::emoji_cluster_len(&chars[i..])
+        } else {
+            None
+        };
+        let Some(cluster_len) = cluster_len else {
+            i += 1;
+            continue;
+        };
+
+        if i > text_start {
+            tokens.push((chars[text_start..i].iter().collect(), false, text_start, i));
+        }
+        tokens.push((
+            chars[i..i + cluster_len].iter().collect(),
+            true,
+            i,
+            i + cluster_len,
+        ));
+        i += cluster_len;
+        text_start = i;
+    }
+    if text_start < chars.len() {
+        tokens.push((
+            chars[text_start..].iter().collect(),
+            false,
+            text_start,
+            chars.len(),
+        ));
+    }
+    Ok(tokens)
+}
+
 /// A Python module implemented in Rust.
 #[pymodule]
 fn simple_unicode_normalization_forms(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(basic_string_clean, m)?)?;
     m.add_function(wrap_pyfunction!(remove_emojis, m)?)?;
+    m.add_function(wrap_pyfunction!(demojize, m)?)?;
+    m.add_function(wrap_pyfunction!(emojize, m)?)?;
+    m.add_function(wrap_pyfunction!(analyze, m)?)?;
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::remove_emojis;
+    use super::{analyze, basic_string_clean, demojize, emojize, remove_emojis};
     use std::time::{Duration, Instant};
 
+    #[test]
+    fn demojize_replaces_known_emoji_with_their_cldr_name() {
+        let delimiters = (":".to_string(), ":".to_string());
+        let test_cases: [(&str, &str); 4] = [
+            ("I \u{2764}\u{FE0F} Rust", "I :red_heart: Rust"),
+            ("nice \u{1F44D} work", "nice :thumbs_up: work"),
+            ("age \u{35}\u{FE0F}\u{20E3}", "age :keycap_five:"),
+            ("plain text", "plain text"),
+        ];
+        for (input, expected) in test_cases {
+            assert_eq!(
+                expected,
+                demojize(input.to_string(), delimiters.clone(), None, None).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn emojize_replaces_known_names_and_leaves_unknown_tokens_alone() {
+        let delimiters = (":".to_string(), ":".to_string());
+        assert_eq!(
+            "I \u{2764} Rust",
+            emojize("I :red_heart: Rust".to_string(), delimiters.clone()).unwrap()
+        );
+        assert_eq!(
+            "unknown :not_a_real_emoji: stays",
+            emojize("unknown :not_a_real_emoji: stays".to_string(), delimiters).unwrap()
+        );
+    }
+
+    #[test]
+    fn remove_emojis_consumes_zwj_sequences_and_skin_tone_modifiers_atomically() {
+        // family_man_woman_girl: man + ZWJ + woman + ZWJ + girl, removed as one cluster.
+        assert_eq!(
+            "family:",
+            remove_emojis(
+                "family:\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}".to_string()
+            )
+            .unwrap()
+        );
+        // A skin-tone modifier never survives on its own once its base emoji is gone.
+        assert_eq!(
+            "waves",
+            remove_emojis("waves\u{1F44D}\u{1F3FD}".to_string()).unwrap()
+        );
+    }
+
+    #[test]
+    fn analyze_splits_text_and_emoji_runs_with_char_offsets() {
+        let tokens = analyze("hi\u{1F44D}\u{1F3FD}!".to_string()).unwrap();
+        assert_eq!(
+            tokens,
+            vec![
+                ("hi".to_string(), false, 0, 2),
+                ("\u{1F44D}\u{1F3FD}".to_string(), true, 2, 4),
+                ("!".to_string(), false, 4, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn basic_string_clean_applies_allow_list_whitespace_collapse_and_emoji_removal_together() {
+        let result = basic_string_clean(
+            "a\tb\n\n  c \u{1F600}".to_string(),
+            true,
+            true,
+            true,
+            true,
+            false,
+            (":".to_string(), ":".to_string()),
+            vec![],
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!("a\tb\n\n c", result);
+    }
+
+    #[test]
+    fn basic_string_clean_strips_configured_general_categories() {
+        let result = basic_string_clean(
+            "Hello! $100".to_string(),
+            false,
+            true,
+            false,
+            false,
+            false,
+            (":".to_string(), ":".to_string()),
+            vec!["Po".to_string(), "Sc".to_string()],
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!("Hello 100", result);
+    }
+
+    #[test]
+    fn basic_string_clean_keeps_emoji_newer_than_max_emoji_version() {
+        // U+231A (0.6) is stripped; U+2194 (3.0) is newer than the 0.6 cutoff
+        // and is preserved instead of being removed.
+        let result = basic_string_clean(
+            "\u{231A}\u{2194}".to_string(),
+            false,
+            true,
+            false,
+            true,
+            false,
+            (":".to_string(), ":".to_string()),
+            vec![],
+            None,
+            Some(0.6),
+        )
+        .unwrap();
+        assert_eq!("\u{2194}", result);
+    }
+
+    #[test]
+    fn basic_string_clean_preserves_supplementary_plane_emoji_below_min_emoji_version() {
+        // U+1F44D (thumbs up, version 1.0) is below the 2.0 cutoff, so it
+        // must survive as-is rather than being dropped by `is_char_to_avoid`
+        // (which rejects every non-BMP scalar).
+        let result = basic_string_clean(
+            "before\u{1F44D}after".to_string(),
+            false,
+            true,
+            false,
+            true,
+            false,
+            (":".to_string(), ":".to_string()),
+            vec![],
+            Some(2.0),
+            None,
+        )
+        .unwrap();
+        assert_eq!("before\u{1F44D}after", result);
+    }
+
+    #[test]
+    fn basic_string_clean_preserves_zwj_sequences_below_min_emoji_version_without_orphaning_joiners(
+    ) {
+        // The whole family_man_woman_girl cluster (version 1.0) is below the
+        // 2.0 cutoff and must be preserved atomically — not just the base
+        // scalars while stray `U+200D` joiners are left behind.
+        let result = basic_string_clean(
+            "before\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}after".to_string(),
+            false,
+            true,
+            false,
+            true,
+            false,
+            (":".to_string(), ":".to_string()),
+            vec![],
+            Some(2.0),
+            None,
+        )
+        .unwrap();
+        assert_eq!("before\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}after", result);
+    }
+
+    #[test]
+    fn basic_string_clean_still_drops_orphan_variation_selectors_outside_version_window() {
+        // A bare `U+FE0F` with no base emoji of its own has no recognised
+        // cluster (`emoji_cluster_len` returns `None` for it); the version
+        // window must not make it "preserved" verbatim — it's always noise.
+        let result = basic_string_clean(
+            "a\u{FE0F}b".to_string(),
+            false,
+            true,
+            false,
+            true,
+            false,
+            (":".to_string(), ":".to_string()),
+            vec![],
+            Some(2.0),
+            None,
+        )
+        .unwrap();
+        assert_eq!("ab", result);
+    }
+
     #[test]
     fn correctness() {
         let test_cases: [(&str, Option<&str>); 18] = [